@@ -0,0 +1,142 @@
+//! A fixed-size Bloom filter used to skip partitions that provably don't
+//! contain a series, without ever producing a false negative.
+
+use crate::TsinkError;
+
+/// An FNV-1a variant with the seed mixed into the offset basis, so calling
+/// it with two seeds gives the two hashes used for double hashing
+/// (Kirsch-Mitzenmacher).
+fn hash_with_seed(key: &[u8], seed: u64) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET ^ seed;
+    for &byte in key {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// A Bloom filter sized for an expected number of items and a target
+/// false-positive rate. Never produces false negatives: if `might_contain`
+/// returns `false`, the key was definitely never inserted.
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: usize,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Size a filter for `expected_items` insertions at roughly
+    /// `false_positive_rate` (e.g. `0.01` for 1%).
+    pub fn with_sizing(expected_items: usize, false_positive_rate: f64) -> Self {
+        let expected_items = expected_items.max(1);
+        let false_positive_rate = false_positive_rate.clamp(1e-6, 0.5);
+
+        let ln2_sq = std::f64::consts::LN_2 * std::f64::consts::LN_2;
+        let num_bits =
+            (-(expected_items as f64) * false_positive_rate.ln() / ln2_sq).ceil() as usize;
+        let num_bits = num_bits.max(64);
+
+        let num_hashes = ((num_bits as f64 / expected_items as f64) * std::f64::consts::LN_2)
+            .round()
+            .max(1.0) as u32;
+
+        Self {
+            bits: vec![0u64; num_bits.div_ceil(64)],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn bit_indices(&self, key: &[u8]) -> impl Iterator<Item = usize> + '_ {
+        let h1 = hash_with_seed(key, 0);
+        let h2 = hash_with_seed(key, 0x9E3779B97F4A7C15);
+        (0..self.num_hashes).map(move |i| {
+            (h1.wrapping_add((i as u64).wrapping_mul(h2)) as usize) % self.num_bits
+        })
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        for index in self.bit_indices(key).collect::<Vec<_>>() {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    /// `false` means the key was definitely never inserted. `true` means it
+    /// probably was, subject to the configured false-positive rate.
+    pub fn might_contain(&self, key: &[u8]) -> bool {
+        self.bit_indices(key).all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(16 + self.bits.len() * 8);
+        buf.extend_from_slice(&(self.num_bits as u64).to_le_bytes());
+        buf.extend_from_slice(&(self.num_hashes as u64).to_le_bytes());
+        for word in &self.bits {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf
+    }
+
+    pub(crate) fn from_bytes(buf: &[u8]) -> Result<Self, TsinkError> {
+        let corrupt = || TsinkError::Corrupted("invalid bloom filter encoding".into());
+
+        let num_bits = u64::from_le_bytes(buf.get(0..8).ok_or_else(corrupt)?.try_into().unwrap()) as usize;
+        let num_hashes = u64::from_le_bytes(buf.get(8..16).ok_or_else(corrupt)?.try_into().unwrap()) as u32;
+
+        let word_count = num_bits.div_ceil(64);
+        let mut bits = Vec::with_capacity(word_count);
+        for i in 0..word_count {
+            let start = 16 + i * 8;
+            let word = u64::from_le_bytes(buf.get(start..start + 8).ok_or_else(corrupt)?.try_into().unwrap());
+            bits.push(word);
+        }
+
+        Ok(Self { bits, num_bits, num_hashes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_false_negative() {
+        let mut filter = BloomFilter::with_sizing(1000, 0.01);
+        let keys: Vec<String> = (0..1000).map(|i| format!("series-{i}")).collect();
+        for key in &keys {
+            filter.insert(key.as_bytes());
+        }
+        for key in &keys {
+            assert!(filter.might_contain(key.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn absent_keys_are_usually_rejected() {
+        let mut filter = BloomFilter::with_sizing(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(format!("series-{i}").as_bytes());
+        }
+
+        let false_positives = (1000..2000)
+            .filter(|i| filter.might_contain(format!("series-{i}").as_bytes()))
+            .count();
+        // Sized for a 1% false-positive rate; leave generous slack.
+        assert!(false_positives < 100, "saw {false_positives} false positives out of 1000");
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut filter = BloomFilter::with_sizing(100, 0.01);
+        filter.insert(b"a");
+        filter.insert(b"b");
+
+        let restored = BloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+        assert!(restored.might_contain(b"a"));
+        assert!(restored.might_contain(b"b"));
+        assert!(!restored.might_contain(b"definitely-not-inserted"));
+    }
+}
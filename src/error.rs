@@ -0,0 +1,44 @@
+use std::fmt;
+use std::io;
+
+/// Errors returned by storage and WAL operations.
+#[derive(Debug)]
+pub enum TsinkError {
+    /// `select` was called with `start > end`.
+    InvalidTimeRange { start: i64, end: i64 },
+    /// A metric name is required but was empty.
+    MetricRequired,
+    /// An I/O error occurred while reading or writing on-disk state.
+    Io(io::Error),
+    /// On-disk data failed a consistency check (e.g. a bad checksum or a
+    /// truncated record) and could not be recovered.
+    Corrupted(String),
+}
+
+impl fmt::Display for TsinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TsinkError::InvalidTimeRange { start, end } => {
+                write!(f, "invalid time range: start {start} is after end {end}")
+            }
+            TsinkError::MetricRequired => write!(f, "metric name is required"),
+            TsinkError::Io(err) => write!(f, "io error: {err}"),
+            TsinkError::Corrupted(msg) => write!(f, "corrupted data: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for TsinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TsinkError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TsinkError {
+    fn from(err: io::Error) -> Self {
+        TsinkError::Io(err)
+    }
+}
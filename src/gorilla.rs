@@ -0,0 +1,331 @@
+//! Gorilla-style delta-of-delta timestamp and XOR value compression for a
+//! single series' points, as described in Facebook's Gorilla paper.
+
+use crate::DataPoint;
+
+/// MSB-first bit writer used by the Gorilla encoder.
+struct BitWriter {
+    buf: Vec<u8>,
+    /// Number of bits used in the last byte of `buf` (0 means `buf` is empty
+    /// or the last byte is full).
+    bit_pos: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new(), bit_pos: 0 }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.bit_pos == 0 {
+            self.buf.push(0);
+        }
+        if bit {
+            let last = self.buf.last_mut().unwrap();
+            *last |= 1 << (7 - self.bit_pos);
+        }
+        self.bit_pos = (self.bit_pos + 1) % 8;
+    }
+
+    /// Write the low `n_bits` of `value`, most-significant bit first.
+    fn write_bits(&mut self, value: u64, n_bits: u32) {
+        for i in (0..n_bits).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// MSB-first bit reader, the inverse of [`BitWriter`].
+struct BitReader<'a> {
+    buf: &'a [u8],
+    byte_pos: usize,
+    bit_pos: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, byte_pos: 0, bit_pos: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = *self.buf.get(self.byte_pos)?;
+        let bit = (byte >> (7 - self.bit_pos)) & 1 == 1;
+        self.bit_pos += 1;
+        if self.bit_pos == 8 {
+            self.bit_pos = 0;
+            self.byte_pos += 1;
+        }
+        Some(bit)
+    }
+
+    fn read_bits(&mut self, n_bits: u32) -> Option<u64> {
+        let mut value = 0u64;
+        for _ in 0..n_bits {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Some(value)
+    }
+}
+
+/// Sign-extend the low `n_bits` of `value` into an `i64`.
+fn sign_extend(value: u64, n_bits: u32) -> i64 {
+    let shift = 64 - n_bits;
+    ((value << shift) as i64) >> shift
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bits) - 1
+    }
+}
+
+/// Leading/trailing zero-count of the block reused from the previous XOR,
+/// used to decide whether a value's meaningful bits fit in the last block.
+#[derive(Clone, Copy)]
+struct ValueBlock {
+    leading: u32,
+    trailing: u32,
+}
+
+impl ValueBlock {
+    const NONE: ValueBlock = ValueBlock { leading: u32::MAX, trailing: 0 };
+}
+
+/// Returns `false` if `dod` doesn't fit any of the format's control-bit
+/// widths (the widest being a signed 32-bit value), in which case the caller
+/// must fall back to raw storage rather than have this truncate it.
+fn dod_fits(dod: i64) -> bool {
+    (i32::MIN as i64..=i32::MAX as i64).contains(&dod)
+}
+
+fn encode_timestamp_dod(writer: &mut BitWriter, dod: i64) {
+    debug_assert!(dod_fits(dod), "caller must check dod_fits before encoding");
+    if dod == 0 {
+        writer.write_bit(false);
+    } else if (-63..=64).contains(&dod) {
+        writer.write_bits(0b10, 2);
+        writer.write_bits(dod as u64 & mask(7), 7);
+    } else if (-255..=256).contains(&dod) {
+        writer.write_bits(0b110, 3);
+        writer.write_bits(dod as u64 & mask(9), 9);
+    } else if (-2047..=2048).contains(&dod) {
+        writer.write_bits(0b1110, 4);
+        writer.write_bits(dod as u64 & mask(12), 12);
+    } else {
+        writer.write_bits(0b1111, 4);
+        writer.write_bits(dod as u64 & mask(32), 32);
+    }
+}
+
+fn decode_timestamp_dod(reader: &mut BitReader) -> Option<i64> {
+    // A short bucket of `n` bits holds [-(2^(n-1) - 1), 2^(n-1)], so the bit
+    // pattern two's complement reads as -2^(n-1) stands for +2^(n-1).
+    let bucket = |value: u64, n_bits: u32| {
+        let dod = sign_extend(value, n_bits);
+        if dod == -(1i64 << (n_bits - 1)) { -dod } else { dod }
+    };
+    if !reader.read_bit()? {
+        return Some(0);
+    }
+    if !reader.read_bit()? {
+        return Some(bucket(reader.read_bits(7)?, 7));
+    }
+    if !reader.read_bit()? {
+        return Some(bucket(reader.read_bits(9)?, 9));
+    }
+    if !reader.read_bit()? {
+        return Some(bucket(reader.read_bits(12)?, 12));
+    }
+    Some(sign_extend(reader.read_bits(32)?, 32))
+}
+
+/// Encode `value_bits` as the XOR against `prev_bits`, updating `block` with
+/// the leading/trailing zero-count of this value when a new one is written.
+fn encode_value(writer: &mut BitWriter, value_bits: u64, prev_bits: u64, block: &mut ValueBlock) {
+    let xor = value_bits ^ prev_bits;
+    if xor == 0 {
+        writer.write_bit(false);
+        return;
+    }
+    writer.write_bit(true);
+
+    let leading = xor.leading_zeros().min(31);
+    let trailing = xor.trailing_zeros();
+
+    if block.leading != u32::MAX && leading >= block.leading && trailing >= block.trailing {
+        writer.write_bit(false);
+        let block_len = 64 - block.leading - block.trailing;
+        writer.write_bits(xor >> block.trailing, block_len);
+    } else {
+        writer.write_bit(true);
+        let meaningful_len = 64 - leading - trailing;
+        writer.write_bits(leading as u64, 5);
+        // Meaningful length is in 1..=64, stored as length - 1 so it fits 6 bits.
+        writer.write_bits((meaningful_len - 1) as u64, 6);
+        writer.write_bits(xor >> trailing, meaningful_len);
+        *block = ValueBlock { leading, trailing };
+    }
+}
+
+fn decode_value(reader: &mut BitReader, prev_bits: u64, block: &mut ValueBlock) -> Option<u64> {
+    if !reader.read_bit()? {
+        return Some(prev_bits);
+    }
+
+    if !reader.read_bit()? {
+        let block_len = 64 - block.leading - block.trailing;
+        let meaningful = reader.read_bits(block_len)?;
+        Some(prev_bits ^ (meaningful << block.trailing))
+    } else {
+        let leading = reader.read_bits(5)? as u32;
+        let meaningful_len = reader.read_bits(6)? as u32 + 1;
+        let meaningful = reader.read_bits(meaningful_len)?;
+        let trailing = 64 - leading - meaningful_len;
+        *block = ValueBlock { leading, trailing };
+        Some(prev_bits ^ (meaningful << trailing))
+    }
+}
+
+/// Encode a series' points (already sorted by timestamp) into a Gorilla
+/// bitstream. Returns `None` if the series is too short to benefit (fewer
+/// than 2 points) or if any delta-of-delta falls outside the signed 32-bit
+/// range the format can represent, in which case callers should fall back
+/// to raw storage rather than have a huge gap between points silently
+/// truncated into a wrong timestamp.
+pub(crate) fn encode(points: &[DataPoint]) -> Option<Vec<u8>> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mut delta = points[1].timestamp - points[0].timestamp;
+    for window in points[1..].windows(2) {
+        let (prev, cur) = (window[0], window[1]);
+        let next_delta = cur.timestamp - prev.timestamp;
+        if !dod_fits(next_delta - delta) {
+            return None;
+        }
+        delta = next_delta;
+    }
+
+    let mut writer = BitWriter::new();
+    writer.write_bits(points.len() as u64, 32);
+    writer.write_bits(points[0].timestamp as u64, 64);
+    writer.write_bits(points[0].value.to_bits(), 64);
+
+    let mut delta = points[1].timestamp - points[0].timestamp;
+    writer.write_bits(delta as u64, 64);
+
+    let mut block = ValueBlock::NONE;
+    let mut prev_value_bits = points[0].value.to_bits();
+    encode_value(&mut writer, points[1].value.to_bits(), prev_value_bits, &mut block);
+    prev_value_bits = points[1].value.to_bits();
+
+    for window in points[1..].windows(2) {
+        let (prev, cur) = (window[0], window[1]);
+        let dod = (cur.timestamp - prev.timestamp) - delta;
+        encode_timestamp_dod(&mut writer, dod);
+        delta = cur.timestamp - prev.timestamp;
+
+        let cur_bits = cur.value.to_bits();
+        encode_value(&mut writer, cur_bits, prev_value_bits, &mut block);
+        prev_value_bits = cur_bits;
+    }
+
+    Some(writer.into_bytes())
+}
+
+/// Decode a Gorilla bitstream produced by [`encode`] back into points.
+pub(crate) fn decode(data: &[u8]) -> Option<Vec<DataPoint>> {
+    let mut reader = BitReader::new(data);
+    let count = reader.read_bits(32)? as usize;
+    if count < 2 {
+        return None;
+    }
+
+    let first_timestamp = reader.read_bits(64)? as i64;
+    let first_value = f64::from_bits(reader.read_bits(64)?);
+    let mut delta = reader.read_bits(64)? as i64;
+
+    let mut block = ValueBlock::NONE;
+    let second_value_bits = decode_value(&mut reader, first_value.to_bits(), &mut block)?;
+
+    let mut points = Vec::with_capacity(count);
+    points.push(DataPoint::new(first_timestamp, first_value));
+    let second_timestamp = first_timestamp + delta;
+    points.push(DataPoint::new(second_timestamp, f64::from_bits(second_value_bits)));
+
+    let mut timestamp = second_timestamp;
+    let mut prev_value_bits = second_value_bits;
+
+    for _ in 2..count {
+        let dod = decode_timestamp_dod(&mut reader)?;
+        delta += dod;
+        timestamp += delta;
+
+        let value_bits = decode_value(&mut reader, prev_value_bits, &mut block)?;
+        points.push(DataPoint::new(timestamp, f64::from_bits(value_bits)));
+        prev_value_bits = value_bits;
+    }
+
+    Some(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_regular_series() {
+        let points: Vec<DataPoint> = (0..200)
+            .map(|i| DataPoint::new(1_600_000_000 + i * 10, 20.0 + (i % 7) as f64 * 0.5))
+            .collect();
+
+        let encoded = encode(&points).expect("series is long enough to encode");
+        let decoded = decode(&encoded).expect("valid bitstream decodes");
+        assert_eq!(decoded, points);
+    }
+
+    #[test]
+    fn round_trips_irregular_deltas_and_values() {
+        let points = vec![
+            DataPoint::new(100, 1.0),
+            DataPoint::new(105, 1.0),
+            DataPoint::new(106, 2.5),
+            DataPoint::new(206, 2.5),
+            DataPoint::new(5000, -3.25),
+            DataPoint::new(500_000, 123_456.789),
+        ];
+
+        let encoded = encode(&points).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded, points);
+    }
+
+    #[test]
+    fn too_short_series_is_not_encoded() {
+        assert!(encode(&[DataPoint::new(0, 1.0)]).is_none());
+        assert!(encode(&[]).is_none());
+    }
+
+    #[test]
+    fn falls_back_when_delta_of_delta_overflows_32_bits() {
+        let points = vec![
+            DataPoint::new(0, 1.0),
+            DataPoint::new(1, 1.0),
+            DataPoint::new(2, 1.0),
+            DataPoint::new(10_000_000_000_000, 1.0),
+            DataPoint::new(10_000_000_000_001, 1.0),
+        ];
+
+        assert!(
+            encode(&points).is_none(),
+            "a delta-of-delta this large can't be represented and must fall back to raw"
+        );
+    }
+}
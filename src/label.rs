@@ -0,0 +1,36 @@
+/// A single key/value label attached to a metric to identify a series.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label {
+    pub name: String,
+    pub value: String,
+}
+
+impl Label {
+    /// Create a label from any string-like key and value.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// Build the identity of a series from its metric name and labels.
+///
+/// Labels are sorted so that the same set, regardless of insertion order,
+/// always produces the same key. This key is what partitions index series
+/// by and what per-partition Bloom filters are keyed on.
+pub(crate) fn series_key(metric: &str, labels: &[Label]) -> String {
+    let mut sorted: Vec<&Label> = labels.iter().collect();
+    sorted.sort();
+
+    let mut key = String::with_capacity(metric.len() + sorted.len() * 16);
+    key.push_str(metric);
+    for label in sorted {
+        key.push('\0');
+        key.push_str(&label.name);
+        key.push('\0');
+        key.push_str(&label.value);
+    }
+    key
+}
@@ -0,0 +1,27 @@
+//! tsink: an embeddable time series storage engine.
+
+mod bloom;
+mod crc32;
+mod error;
+mod gorilla;
+mod label;
+mod partition;
+mod point;
+mod storage;
+mod time;
+pub mod wal;
+
+pub use error::TsinkError;
+pub use label::Label;
+pub use partition::{BloomFilterConfig, PartitionCodec};
+pub use point::{DataPoint, Row};
+pub use storage::{SelectCursor, Storage, StorageBuilder};
+
+/// The unit timestamps are interpreted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampPrecision {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
@@ -0,0 +1,382 @@
+//! In-memory and on-disk partitions. A partition covers a contiguous range
+//! of time and stores, per series, the points that fall in that range.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::bloom::BloomFilter;
+use crate::gorilla;
+use crate::label::series_key;
+use crate::{DataPoint, Row, TsinkError};
+
+const DATA_FILE: &str = "data.bin";
+const BLOOM_FILE: &str = "bloom.bin";
+
+/// Points per on-disk block. Series are encoded (and later decoded) one
+/// block at a time, so a scan only ever has to hold a single block's worth
+/// of decoded points in memory rather than a whole series.
+const BLOCK_POINTS: usize = 120;
+
+/// Bloom filter sizing for partitions flushed to disk.
+#[derive(Debug, Clone, Copy)]
+pub struct BloomFilterConfig {
+    pub expected_series: usize,
+    pub false_positive_rate: f64,
+}
+
+impl Default for BloomFilterConfig {
+    fn default() -> Self {
+        Self { expected_series: 1024, false_positive_rate: 0.01 }
+    }
+}
+
+/// Encoding used to persist a single series within a partition.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionCodec {
+    /// Store `(timestamp, value)` pairs verbatim.
+    Raw,
+    /// Gorilla delta-of-delta timestamps + XOR'd values; falls back to
+    /// [`PartitionCodec::Raw`] per-block when a block is too short, its
+    /// deltas too irregular to encode, or its encoding no smaller than raw.
+    #[default]
+    Gorilla,
+}
+
+const CODEC_RAW: u8 = 0;
+const CODEC_GORILLA: u8 = 1;
+
+/// An open, mutable partition that buffers recently inserted points.
+pub(crate) struct MemoryPartition {
+    series: Mutex<BTreeMap<String, Vec<DataPoint>>>,
+    min_timestamp: Mutex<i64>,
+    max_timestamp: Mutex<i64>,
+}
+
+impl MemoryPartition {
+    pub(crate) fn new() -> Self {
+        Self {
+            series: Mutex::new(BTreeMap::new()),
+            min_timestamp: Mutex::new(i64::MAX),
+            max_timestamp: Mutex::new(i64::MIN),
+        }
+    }
+
+    pub(crate) fn insert_rows(&self, rows: &[Row]) {
+        let mut series = self.series.lock().unwrap();
+        let mut min_timestamp = self.min_timestamp.lock().unwrap();
+        let mut max_timestamp = self.max_timestamp.lock().unwrap();
+
+        for row in rows {
+            let key = series_key(row.metric(), row.labels());
+            let point = row.data_point();
+            series.entry(key).or_default().push(point);
+            *min_timestamp = (*min_timestamp).min(point.timestamp);
+            *max_timestamp = (*max_timestamp).max(point.timestamp);
+        }
+    }
+
+    pub(crate) fn min_timestamp(&self) -> i64 {
+        *self.min_timestamp.lock().unwrap()
+    }
+
+    pub(crate) fn max_timestamp(&self) -> i64 {
+        *self.max_timestamp.lock().unwrap()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.series.lock().unwrap().is_empty()
+    }
+
+    /// Drop all buffered points, e.g. after they have been durably flushed.
+    pub(crate) fn clear(&self) {
+        self.series.lock().unwrap().clear();
+        *self.min_timestamp.lock().unwrap() = i64::MAX;
+        *self.max_timestamp.lock().unwrap() = i64::MIN;
+    }
+
+    pub(crate) fn select(
+        &self,
+        metric: &str,
+        labels: &[crate::Label],
+        start: i64,
+        end: i64,
+    ) -> Box<dyn Iterator<Item = Result<DataPoint, TsinkError>> + Send> {
+        let key = series_key(metric, labels);
+        let series = self.series.lock().unwrap();
+        let mut points: Vec<DataPoint> = series
+            .get(&key)
+            .map(|points| {
+                points
+                    .iter()
+                    .copied()
+                    .filter(|p| p.timestamp >= start && p.timestamp <= end)
+                    .collect()
+            })
+            .unwrap_or_default();
+        points.sort_by_key(|p| p.timestamp);
+        Box::new(points.into_iter().map(Ok))
+    }
+
+    /// Persist this partition to `dir`, encoding each series as a sequence
+    /// of independently-decodable blocks with `codec`, and building a Bloom
+    /// filter over its series keys per `bloom_config`. A block that is too
+    /// short, whose timestamps don't fit the Gorilla format, or whose Gorilla
+    /// encoding is no smaller than raw, falls back to raw storage for that
+    /// block.
+    pub(crate) fn flush(
+        &self,
+        dir: &Path,
+        codec: PartitionCodec,
+        bloom_config: BloomFilterConfig,
+    ) -> Result<DiskPartition, TsinkError> {
+        fs::create_dir_all(dir)?;
+        let series = self.series.lock().unwrap();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.min_timestamp().to_le_bytes());
+        buf.extend_from_slice(&self.max_timestamp().to_le_bytes());
+        buf.extend_from_slice(&(series.len() as u32).to_le_bytes());
+
+        let mut filter = BloomFilter::with_sizing(
+            series.len().max(bloom_config.expected_series),
+            bloom_config.false_positive_rate,
+        );
+
+        for (key, points) in series.iter() {
+            filter.insert(key.as_bytes());
+            let mut sorted = points.clone();
+            sorted.sort_by_key(|p| p.timestamp);
+
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+
+            let blocks: Vec<&[DataPoint]> = sorted.chunks(BLOCK_POINTS).collect();
+            buf.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+
+            for block in blocks {
+                let (used_codec, payload) = match codec {
+                    PartitionCodec::Gorilla => {
+                        let raw = encode_raw(block);
+                        match gorilla::encode(block) {
+                            Some(bytes) if bytes.len() < raw.len() => (CODEC_GORILLA, bytes),
+                            _ => (CODEC_RAW, raw),
+                        }
+                    }
+                    PartitionCodec::Raw => (CODEC_RAW, encode_raw(block)),
+                };
+
+                buf.extend_from_slice(&block[0].timestamp.to_le_bytes());
+                buf.extend_from_slice(&block[block.len() - 1].timestamp.to_le_bytes());
+                buf.push(used_codec);
+                buf.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+                buf.extend_from_slice(&payload);
+            }
+        }
+
+        let path = dir.join(DATA_FILE);
+        let mut file = File::create(&path)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+
+        let bloom_path = dir.join(BLOOM_FILE);
+        let mut bloom_file = File::create(&bloom_path)?;
+        bloom_file.write_all(&filter.to_bytes())?;
+        bloom_file.sync_all()?;
+
+        DiskPartition::open(dir)
+    }
+}
+
+fn encode_raw(points: &[DataPoint]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(points.len() * 16);
+    for point in points {
+        buf.extend_from_slice(&point.timestamp.to_le_bytes());
+        buf.extend_from_slice(&point.value.to_bits().to_le_bytes());
+    }
+    buf
+}
+
+fn decode_raw(buf: &[u8]) -> Vec<DataPoint> {
+    buf.chunks_exact(16)
+        .map(|chunk| {
+            let timestamp = i64::from_le_bytes(chunk[0..8].try_into().unwrap());
+            let value = f64::from_bits(u64::from_le_bytes(chunk[8..16].try_into().unwrap()));
+            DataPoint::new(timestamp, value)
+        })
+        .collect()
+}
+
+fn decode_block(codec: u8, payload: &[u8]) -> Result<Vec<DataPoint>, TsinkError> {
+    match codec {
+        CODEC_GORILLA => gorilla::decode(payload).ok_or_else(|| TsinkError::Corrupted("invalid gorilla block".into())),
+        _ => Ok(decode_raw(payload)),
+    }
+}
+
+/// Byte range and time span of one on-disk block, used to skip decoding
+/// blocks that can't overlap a query's time range.
+#[derive(Clone, Copy)]
+struct BlockMeta {
+    min_timestamp: i64,
+    max_timestamp: i64,
+    codec: u8,
+    start: usize,
+    end: usize,
+}
+
+/// Per-series index into a partition's data file: which byte ranges hold
+/// which blocks, without any of them decoded yet.
+struct SeriesIndex {
+    blocks: Vec<BlockMeta>,
+}
+
+/// A read-only, flushed partition. On load, only the block index is parsed;
+/// series data stays as raw bytes until `select` decodes the specific
+/// blocks it needs, one at a time.
+pub(crate) struct DiskPartition {
+    min_timestamp: i64,
+    max_timestamp: i64,
+    data: Arc<[u8]>,
+    series: HashMap<String, SeriesIndex>,
+    filter: BloomFilter,
+}
+
+impl DiskPartition {
+    /// Load a previously flushed partition back from `dir`, indexing block
+    /// boundaries without decoding any of them.
+    pub(crate) fn open(dir: &Path) -> Result<Self, TsinkError> {
+        let path = dir.join(DATA_FILE);
+        let data: Arc<[u8]> = Arc::from(fs::read(&path)?.into_boxed_slice());
+
+        let min_timestamp = i64::from_le_bytes(data[0..8].try_into().unwrap());
+        let max_timestamp = i64::from_le_bytes(data[8..16].try_into().unwrap());
+        let series_count = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+
+        let mut cursor = 20usize;
+        let mut series = HashMap::with_capacity(series_count);
+        for _ in 0..series_count {
+            let key_len = u32::from_le_bytes(data[cursor..cursor + 4].try_into().unwrap()) as usize;
+            cursor += 4;
+            let key = String::from_utf8(data[cursor..cursor + key_len].to_vec())
+                .map_err(|_| TsinkError::Corrupted("invalid utf8 in partition index".into()))?;
+            cursor += key_len;
+
+            let block_count = u32::from_le_bytes(data[cursor..cursor + 4].try_into().unwrap()) as usize;
+            cursor += 4;
+
+            let mut blocks = Vec::with_capacity(block_count);
+            for _ in 0..block_count {
+                let block_min = i64::from_le_bytes(data[cursor..cursor + 8].try_into().unwrap());
+                cursor += 8;
+                let block_max = i64::from_le_bytes(data[cursor..cursor + 8].try_into().unwrap());
+                cursor += 8;
+                let codec = data[cursor];
+                cursor += 1;
+                let _point_count = u32::from_le_bytes(data[cursor..cursor + 4].try_into().unwrap());
+                cursor += 4;
+                let payload_len = u32::from_le_bytes(data[cursor..cursor + 4].try_into().unwrap()) as usize;
+                cursor += 4;
+                let start = cursor;
+                let end = cursor + payload_len;
+                cursor = end;
+
+                blocks.push(BlockMeta { min_timestamp: block_min, max_timestamp: block_max, codec, start, end });
+            }
+
+            series.insert(key, SeriesIndex { blocks });
+        }
+
+        let filter = BloomFilter::from_bytes(&fs::read(dir.join(BLOOM_FILE))?)?;
+
+        Ok(Self {
+            min_timestamp,
+            max_timestamp,
+            data,
+            series,
+            filter,
+        })
+    }
+
+    pub(crate) fn min_timestamp(&self) -> i64 {
+        self.min_timestamp
+    }
+
+    pub(crate) fn max_timestamp(&self) -> i64 {
+        self.max_timestamp
+    }
+
+    /// Select points for `metric`/`labels` as a lazily-decoded cursor,
+    /// first consulting the partition's Bloom filter so a series that never
+    /// appeared here skips straight to an empty iterator. The filter never
+    /// false-negatives, so this is always safe. Blocks outside `[start,
+    /// end]` are skipped without decoding; a matching block is decoded in
+    /// full, yielded from, and dropped before the next one is touched, so
+    /// only one block's points are ever resident at a time.
+    pub(crate) fn select(
+        &self,
+        metric: &str,
+        labels: &[crate::Label],
+        start: i64,
+        end: i64,
+    ) -> Box<dyn Iterator<Item = Result<DataPoint, TsinkError>> + Send> {
+        let key = series_key(metric, labels);
+        if !self.filter.might_contain(key.as_bytes()) {
+            return Box::new(std::iter::empty());
+        }
+        match self.series.get(&key) {
+            Some(index) => Box::new(DiskSeriesCursor {
+                data: Arc::clone(&self.data),
+                blocks: index.blocks.clone().into_iter(),
+                current: Vec::new().into_iter(),
+                start,
+                end,
+            }),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+/// Lazily decodes one block at a time from a single series, in ascending
+/// timestamp order, filtering to `[start, end]` as it goes.
+struct DiskSeriesCursor {
+    data: Arc<[u8]>,
+    blocks: std::vec::IntoIter<BlockMeta>,
+    current: std::vec::IntoIter<DataPoint>,
+    start: i64,
+    end: i64,
+}
+
+impl Iterator for DiskSeriesCursor {
+    type Item = Result<DataPoint, TsinkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.current.next() {
+                return Some(Ok(point));
+            }
+
+            let block = loop {
+                let block = self.blocks.next()?;
+                if block.max_timestamp < self.start || block.min_timestamp > self.end {
+                    continue;
+                }
+                break block;
+            };
+
+            let payload = &self.data[block.start..block.end];
+            let points = match decode_block(block.codec, payload) {
+                Ok(points) => points,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let (start, end) = (self.start, self.end);
+            let filtered: Vec<DataPoint> =
+                points.into_iter().filter(|p| p.timestamp >= start && p.timestamp <= end).collect();
+            self.current = filtered.into_iter();
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::Label;
+
+/// A single `(timestamp, value)` sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataPoint {
+    pub timestamp: i64,
+    pub value: f64,
+}
+
+impl DataPoint {
+    pub fn new(timestamp: i64, value: f64) -> Self {
+        Self { timestamp, value }
+    }
+}
+
+/// A data point tagged with the metric and labels identifying its series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    metric: String,
+    labels: Vec<Label>,
+    data_point: DataPoint,
+}
+
+impl Row {
+    /// Create a row for an unlabeled metric.
+    pub fn new(metric: impl Into<String>, data_point: DataPoint) -> Self {
+        Self {
+            metric: metric.into(),
+            labels: Vec::new(),
+            data_point,
+        }
+    }
+
+    /// Create a row for a metric with labels.
+    pub fn with_labels(metric: impl Into<String>, labels: Vec<Label>, data_point: DataPoint) -> Self {
+        Self {
+            metric: metric.into(),
+            labels,
+            data_point,
+        }
+    }
+
+    pub fn metric(&self) -> &str {
+        &self.metric
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn data_point(&self) -> DataPoint {
+        self.data_point
+    }
+}
@@ -0,0 +1,352 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::partition::{BloomFilterConfig, DiskPartition, MemoryPartition, PartitionCodec};
+use crate::time::{duration_to_units, now_in_precision};
+use crate::wal::{DiskWal, Wal, WalReader, WalSyncPolicy};
+use crate::{DataPoint, Label, Row, TimestampPrecision, TsinkError};
+
+const PARTITIONS_DIR: &str = "partitions";
+const WAL_DIR: &str = "wal";
+
+/// An embedded time series store: in-memory writes, backed by an optional
+/// write-ahead log and flushed into immutable on-disk partitions.
+pub struct Storage {
+    head: MemoryPartition,
+    /// When the current head partition was opened, in `timestamp_precision`
+    /// units, so a partition can be rotated once it has been open for
+    /// `partition_duration` even if its data span hasn't caught up yet.
+    head_opened_at: AtomicI64,
+    disk_partitions: RwLock<Vec<Arc<DiskPartition>>>,
+    wal: Option<Arc<dyn Wal>>,
+    data_path: Option<PathBuf>,
+    partition_duration: Duration,
+    timestamp_precision: TimestampPrecision,
+    codec: PartitionCodec,
+    bloom_config: BloomFilterConfig,
+    next_partition_id: AtomicU64,
+    /// Serializes "decide to rotate -> flush head -> clear head" (held for
+    /// write) against both concurrent rotations and concurrent reads, so a
+    /// `select_cursor` snapshot of `disk_partitions` + `head` is always taken
+    /// atomically with respect to rotation (held for read) — a rotation can
+    /// never complete (and clear `head`) in the gap between a reader
+    /// snapshotting `disk_partitions` and snapshotting `head`, which would
+    /// otherwise make points vanish from that read. Concurrent reads take
+    /// the read side and aren't serialized against each other.
+    rotation_lock: RwLock<()>,
+}
+
+impl Storage {
+    /// Insert a batch of rows. Rows may arrive out of order; `select` always
+    /// returns points sorted by timestamp regardless of insertion order.
+    pub fn insert_rows(&self, rows: &[Row]) -> Result<(), TsinkError> {
+        if let Some(wal) = &self.wal {
+            wal.append_rows(rows)?;
+        }
+
+        // Holding `rotation_lock` for write across the insert as well as the
+        // rotation check keeps a concurrent flush from clearing `head` in
+        // between this insert and the decision of whether to rotate, and
+        // keeps a concurrent reader from snapshotting `head` mid-insert.
+        let _guard = self.rotation_lock.write().unwrap();
+        self.head.insert_rows(rows);
+        self.rotate_if_needed()?;
+        Ok(())
+    }
+
+    /// Select all points for `metric`/`labels` with `start <= timestamp <= end`,
+    /// sorted ascending by timestamp.
+    pub fn select(
+        &self,
+        metric: &str,
+        labels: &[Label],
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<DataPoint>, TsinkError> {
+        self.select_cursor(metric, labels, start, end)?.collect()
+    }
+
+    /// Select points for `metric`/`labels` with `start <= timestamp <= end` as
+    /// a lazily-advanced, ascending-timestamp cursor instead of a fully
+    /// materialized `Vec`. Internally this is a k-way merge: each overlapping
+    /// partition contributes its own (already sorted) point sequence, and the
+    /// cursor repeatedly pops the minimum timestamp across them from a heap.
+    /// Disk partitions decode one block at a time as the cursor advances, so
+    /// a wide scan across many partitions never buffers more than one
+    /// pending block per partition, not the whole series.
+    pub fn select_cursor(
+        &self,
+        metric: &str,
+        labels: &[Label],
+        start: i64,
+        end: i64,
+    ) -> Result<SelectCursor, TsinkError> {
+        if metric.is_empty() {
+            return Err(TsinkError::MetricRequired);
+        }
+        if start > end {
+            return Err(TsinkError::InvalidTimeRange { start, end });
+        }
+
+        // Holding `rotation_lock` for read while snapshotting `disk_partitions`
+        // and `head` keeps a concurrent rotation from completing (pushing the
+        // flushed partition and clearing `head`) in between the two reads,
+        // which would otherwise drop the flushed points from both snapshots
+        // at once. Concurrent `select_cursor` calls only take the read side,
+        // so they aren't serialized against each other.
+        let _guard = self.rotation_lock.read().unwrap();
+
+        let mut sources = Vec::new();
+        for partition in self.disk_partitions.read().unwrap().iter() {
+            if partition.max_timestamp() < start || partition.min_timestamp() > end {
+                continue;
+            }
+            sources.push(partition.select(metric, labels, start, end));
+        }
+        sources.push(self.head.select(metric, labels, start, end));
+
+        Ok(SelectCursor::new(sources))
+    }
+
+    /// Flush any buffered points to disk (if a data path is configured) and
+    /// release the write-ahead log. After `close`, the `Storage` can still be
+    /// used, but it behaves as if freshly reopened.
+    pub fn close(&self) -> Result<(), TsinkError> {
+        {
+            let _guard = self.rotation_lock.write().unwrap();
+            self.flush_head()?;
+        }
+        if let Some(wal) = &self.wal {
+            wal.flush()?;
+            wal.refresh()?;
+        }
+        Ok(())
+    }
+
+    fn rotate_if_needed(&self) -> Result<(), TsinkError> {
+        if self.head.is_empty() {
+            return Ok(());
+        }
+        let span = duration_to_units(self.partition_duration, self.timestamp_precision);
+        let data_span_elapsed = self.head.max_timestamp() - self.head.min_timestamp() >= span;
+        let wall_time_elapsed =
+            now_in_precision(self.timestamp_precision) - self.head_opened_at.load(Ordering::SeqCst) >= span;
+        if !data_span_elapsed && !wall_time_elapsed {
+            return Ok(());
+        }
+        self.flush_head()
+    }
+
+    fn flush_head(&self) -> Result<(), TsinkError> {
+        if self.head.is_empty() {
+            return Ok(());
+        }
+        let Some(data_path) = &self.data_path else {
+            return Ok(());
+        };
+
+        let id = self.next_partition_id.fetch_add(1, Ordering::SeqCst);
+        let dir = data_path.join(PARTITIONS_DIR).join(format!("{id:020}"));
+        let flushed = self.head.flush(&dir, self.codec, self.bloom_config)?;
+
+        let mut partitions = self.disk_partitions.write().unwrap();
+        partitions.push(Arc::new(flushed));
+        partitions.sort_by_key(|p| p.min_timestamp());
+
+        // Points are now durable in a partition file; replace the head with
+        // a fresh, empty one so `select` never double-counts.
+        self.head.clear();
+        self.head_opened_at
+            .store(now_in_precision(self.timestamp_precision), Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+type SelectSource = Peekable<Box<dyn Iterator<Item = Result<DataPoint, TsinkError>> + Send>>;
+
+/// A lazily-advanced cursor over `select`'s result, merging one sorted point
+/// sequence per overlapping partition in ascending timestamp order. Built by
+/// [`Storage::select_cursor`].
+pub struct SelectCursor {
+    sources: Vec<SelectSource>,
+    /// `0` priority for a source whose peeked item is an `Err`, so a decode
+    /// error surfaces immediately instead of being ordered by a timestamp
+    /// that doesn't mean anything for it; `1` plus the real timestamp for an
+    /// `Ok` point.
+    heap: BinaryHeap<Reverse<(u8, i64, usize)>>,
+}
+
+impl SelectCursor {
+    fn new(sources: Vec<Box<dyn Iterator<Item = Result<DataPoint, TsinkError>> + Send>>) -> Self {
+        let mut sources: Vec<SelectSource> = sources.into_iter().map(|source| source.peekable()).collect();
+
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (index, source) in sources.iter_mut().enumerate() {
+            if let Some(key) = Self::peek_key(source, index) {
+                heap.push(Reverse(key));
+            }
+        }
+
+        Self { sources, heap }
+    }
+
+    fn peek_key(source: &mut SelectSource, index: usize) -> Option<(u8, i64, usize)> {
+        match source.peek()? {
+            Ok(point) => Some((1, point.timestamp, index)),
+            Err(_) => Some((0, i64::MIN, index)),
+        }
+    }
+}
+
+impl Iterator for SelectCursor {
+    type Item = Result<DataPoint, TsinkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, _, index)) = self.heap.pop()?;
+        let source = &mut self.sources[index];
+        let item = source.next().expect("heap entry implies a pending item");
+        if let Some(key) = Self::peek_key(source, index) {
+            self.heap.push(Reverse(key));
+        }
+        Some(item)
+    }
+}
+
+/// Builds a [`Storage`], configuring persistence and encoding.
+pub struct StorageBuilder {
+    data_path: Option<PathBuf>,
+    timestamp_precision: TimestampPrecision,
+    partition_duration: Duration,
+    wal_enabled: bool,
+    codec: PartitionCodec,
+    bloom_config: BloomFilterConfig,
+    wal_sync_policy: WalSyncPolicy,
+}
+
+impl StorageBuilder {
+    pub fn new() -> Self {
+        Self {
+            data_path: None,
+            timestamp_precision: TimestampPrecision::Nanoseconds,
+            partition_duration: Duration::from_secs(2 * 60 * 60),
+            wal_enabled: true,
+            codec: PartitionCodec::default(),
+            bloom_config: BloomFilterConfig::default(),
+            wal_sync_policy: WalSyncPolicy::default(),
+        }
+    }
+
+    /// Persist partitions and the write-ahead log under `path`. Without a
+    /// data path, `Storage` is purely in-memory.
+    pub fn with_data_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.data_path = Some(path.into());
+        self
+    }
+
+    pub fn with_timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    /// The span of time (in wall-clock duration, converted via the
+    /// configured timestamp precision) that a single partition covers
+    /// before it is flushed and a new one is opened.
+    pub fn with_partition_duration(mut self, duration: Duration) -> Self {
+        self.partition_duration = duration;
+        self
+    }
+
+    /// Enable or disable the write-ahead log. Has no effect without a data
+    /// path, since there is nowhere to persist WAL segments.
+    pub fn with_wal_enabled(mut self, enabled: bool) -> Self {
+        self.wal_enabled = enabled;
+        self
+    }
+
+    /// Choose the codec used to encode series when a partition is flushed.
+    pub fn with_partition_codec(mut self, codec: PartitionCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Size the Bloom filter built for each partition at flush time, trading
+    /// memory for a lower false-positive rate on `select`'s partition skip.
+    pub fn with_bloom_filter_sizing(mut self, expected_series: usize, false_positive_rate: f64) -> Self {
+        self.bloom_config = BloomFilterConfig { expected_series, false_positive_rate };
+        self
+    }
+
+    /// Choose when rows appended to the write-ahead log become durable. Has
+    /// no effect without a data path, since there is no WAL to sync.
+    pub fn with_wal_sync_policy(mut self, policy: WalSyncPolicy) -> Self {
+        self.wal_sync_policy = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Storage, TsinkError> {
+        let mut disk_partitions = Vec::new();
+        if let Some(data_path) = &self.data_path {
+            fs::create_dir_all(data_path)?;
+            disk_partitions = load_partitions(&data_path.join(PARTITIONS_DIR))?;
+        }
+        disk_partitions.sort_by_key(|p: &Arc<DiskPartition>| p.min_timestamp());
+        let next_partition_id = disk_partitions.len() as u64;
+
+        let head = MemoryPartition::new();
+
+        let wal: Option<Arc<dyn Wal>> = if self.wal_enabled {
+            if let Some(data_path) = &self.data_path {
+                let wal_dir = data_path.join(WAL_DIR);
+                let disk_wal = DiskWal::with_sync_policy(&wal_dir, 0, self.wal_sync_policy)?;
+                let recovered = WalReader::new(&wal_dir)?.read_all()?;
+                if !recovered.is_empty() {
+                    head.insert_rows(&recovered);
+                }
+                Some(disk_wal as Arc<dyn Wal>)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        Ok(Storage {
+            head,
+            head_opened_at: AtomicI64::new(now_in_precision(self.timestamp_precision)),
+            disk_partitions: RwLock::new(disk_partitions),
+            wal,
+            data_path: self.data_path,
+            partition_duration: self.partition_duration,
+            timestamp_precision: self.timestamp_precision,
+            codec: self.codec,
+            bloom_config: self.bloom_config,
+            next_partition_id: AtomicU64::new(next_partition_id),
+            rotation_lock: RwLock::new(()),
+        })
+    }
+}
+
+impl Default for StorageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn load_partitions(dir: &Path) -> Result<Vec<Arc<DiskPartition>>, TsinkError> {
+    fs::create_dir_all(dir)?;
+    let mut partitions = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            partitions.push(Arc::new(DiskPartition::open(&entry.path())?));
+        }
+    }
+    Ok(partitions)
+}
@@ -0,0 +1,571 @@
+//! Write-ahead log used to recover buffered rows that have not yet been
+//! flushed into a partition.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use crate::crc32;
+use crate::{DataPoint, Label, Row, TsinkError};
+
+const WAL_EXTENSION: &str = "wal";
+
+/// Controls when rows appended to a [`DiskWal`] become durable.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum WalSyncPolicy {
+    /// `append_rows` never syncs on its own; durability is up to explicit
+    /// [`Wal::flush`] calls. This is the previous (and default) behavior.
+    #[default]
+    Manual,
+    /// fsync the active segment after every `append_rows` call.
+    Always,
+    /// fsync the active segment on a fixed wall-clock interval from a
+    /// background thread, independent of `append_rows` calls.
+    Periodic(Duration),
+    /// Coalesce fsyncs across concurrent writers: each `append_rows` call
+    /// waits for a single designated writer's fsync to cover its own
+    /// sequence number, so many concurrent batches can share one fsync.
+    Group,
+}
+
+/// Shared bookkeeping for [`WalSyncPolicy::Group`]: `next_seq` is handed out
+/// to each append, and a writer only returns once `durable_seq` has caught
+/// up to the sequence it was given.
+struct GroupState {
+    next_seq: u64,
+    durable_seq: u64,
+    syncing: bool,
+}
+
+/// Records larger than this are split across multiple fragment frames, the
+/// same way a WAL typically bounds how much unframed data a single write
+/// can leave exposed to a torn write.
+const MAX_FRAGMENT_PAYLOAD: usize = 32 * 1024;
+
+/// Position of a chunk of a WAL record within its frame sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FragmentType {
+    /// The entire record fit in a single frame.
+    Full = 0,
+    /// The first chunk of a record split across multiple frames.
+    First = 1,
+    /// A middle chunk of a multi-frame record.
+    Middle = 2,
+    /// The last chunk of a multi-frame record.
+    Last = 3,
+}
+
+impl FragmentType {
+    fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(FragmentType::Full),
+            1 => Some(FragmentType::First),
+            2 => Some(FragmentType::Middle),
+            3 => Some(FragmentType::Last),
+            _ => None,
+        }
+    }
+}
+
+/// Write one `[len][crc32][fragment_type][payload]` frame.
+fn write_frame(writer: &mut impl Write, fragment_type: FragmentType, payload: &[u8]) -> Result<(), TsinkError> {
+    let mut body = Vec::with_capacity(1 + payload.len());
+    body.push(fragment_type as u8);
+    body.extend_from_slice(payload);
+
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(&crc32::checksum(&body).to_le_bytes())?;
+    writer.write_all(&body)?;
+    Ok(())
+}
+
+/// Frame `record` as one `Full` frame, or as `First`/`Middle`*/`Last` frames
+/// chunked to at most [`MAX_FRAGMENT_PAYLOAD`] bytes each.
+fn write_record(writer: &mut impl Write, record: &[u8]) -> Result<(), TsinkError> {
+    if record.len() <= MAX_FRAGMENT_PAYLOAD {
+        return write_frame(writer, FragmentType::Full, record);
+    }
+
+    let chunks: Vec<&[u8]> = record.chunks(MAX_FRAGMENT_PAYLOAD).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let fragment_type = if i == 0 {
+            FragmentType::First
+        } else if i == chunks.len() - 1 {
+            FragmentType::Last
+        } else {
+            FragmentType::Middle
+        };
+        write_frame(writer, fragment_type, chunk)?;
+    }
+    Ok(())
+}
+
+/// One successfully-verified frame read back from a segment.
+struct Frame {
+    fragment_type: FragmentType,
+    payload: Vec<u8>,
+}
+
+/// Read a single frame starting at `data[cursor..]`. Returns `Ok(None)` if
+/// `cursor` is exactly at the end of valid data (a clean EOF), and an error
+/// for anything that looks like a torn write: a length that runs past EOF,
+/// a checksum mismatch, or an unrecognized fragment type.
+fn read_frame(data: &[u8], cursor: usize) -> Result<Option<(Frame, usize)>, TsinkError> {
+    if cursor == data.len() {
+        return Ok(None);
+    }
+    let header = data
+        .get(cursor..cursor + 8)
+        .ok_or_else(|| TsinkError::Corrupted("wal frame header truncated".into()))?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let expected_crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+
+    let body = data
+        .get(cursor + 8..cursor + 8 + len)
+        .ok_or_else(|| TsinkError::Corrupted("wal frame body truncated".into()))?;
+    if crc32::checksum(body) != expected_crc {
+        return Err(TsinkError::Corrupted("wal frame checksum mismatch".into()));
+    }
+    let fragment_type = FragmentType::from_u8(*body.first().ok_or_else(|| {
+        TsinkError::Corrupted("wal frame missing fragment type".into())
+    })?)
+    .ok_or_else(|| TsinkError::Corrupted("wal frame has unknown fragment type".into()))?;
+
+    Ok(Some((
+        Frame { fragment_type, payload: body[1..].to_vec() },
+        cursor + 8 + len,
+    )))
+}
+
+/// A durable log of appended rows, independent of the in-memory partition.
+pub trait Wal: Send + Sync {
+    /// Append a batch of rows to the active segment.
+    fn append_rows(&self, rows: &[Row]) -> Result<(), TsinkError>;
+    /// Flush and fsync buffered writes so they survive a crash.
+    fn flush(&self) -> Result<(), TsinkError>;
+    /// Close the active segment and start a new one.
+    fn punctuate(&self) -> Result<(), TsinkError>;
+    /// Remove the oldest segment, e.g. once its rows are durably flushed.
+    fn remove_oldest(&self) -> Result<(), TsinkError>;
+    /// Drop all segments, e.g. after a full flush to disk partitions.
+    fn refresh(&self) -> Result<(), TsinkError>;
+}
+
+struct Segment {
+    id: u64,
+    path: PathBuf,
+    buffer_size: usize,
+    /// Lazily created so that an untouched segment leaves no file on disk
+    /// (important for `refresh`, which must look like an empty WAL).
+    writer: Option<BufWriter<File>>,
+}
+
+impl Segment {
+    fn writer(&mut self) -> Result<&mut BufWriter<File>, TsinkError> {
+        if self.writer.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            self.writer = Some(if self.buffer_size > 0 {
+                BufWriter::with_capacity(self.buffer_size, file)
+            } else {
+                BufWriter::new(file)
+            });
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    fn flush(&mut self) -> Result<(), TsinkError> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`Wal`] backed by a directory of numbered segment files.
+pub struct DiskWal {
+    dir: PathBuf,
+    buffer_size: usize,
+    active: Mutex<Segment>,
+    sync_policy: WalSyncPolicy,
+    group: Mutex<GroupState>,
+    group_cv: Condvar,
+    /// Signals the [`WalSyncPolicy::Periodic`] background thread to stop;
+    /// joined in `Drop` so a `DiskWal` never outlives its own syncer thread.
+    periodic_stop: Arc<AtomicBool>,
+    periodic_thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}.{WAL_EXTENSION}"))
+}
+
+/// List segment files in `dir`, sorted by segment id.
+fn segment_files(dir: &Path) -> Result<Vec<(u64, PathBuf)>, TsinkError> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().map(|ext| ext == WAL_EXTENSION).unwrap_or(false) {
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                segments.push((id, path));
+            }
+        }
+    }
+    segments.sort_by_key(|(id, _)| *id);
+    Ok(segments)
+}
+
+impl DiskWal {
+    /// Open (or create) the WAL directory and start appending to a fresh
+    /// segment. `buffer_size` is the writer's internal buffer capacity; `0`
+    /// uses a sensible default. Rows are not synced on their own; pair with
+    /// explicit [`Wal::flush`] calls, or use [`DiskWal::with_sync_policy`]
+    /// for an automatic durability policy.
+    pub fn new(dir: impl AsRef<Path>, buffer_size: usize) -> Result<Arc<Self>, TsinkError> {
+        Self::with_sync_policy(dir, buffer_size, WalSyncPolicy::Manual)
+    }
+
+    /// Like [`DiskWal::new`], but with an explicit [`WalSyncPolicy`]
+    /// controlling when appended rows become durable.
+    pub fn with_sync_policy(
+        dir: impl AsRef<Path>,
+        buffer_size: usize,
+        sync_policy: WalSyncPolicy,
+    ) -> Result<Arc<Self>, TsinkError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let next_id = segment_files(&dir)?.last().map(|(id, _)| id + 1).unwrap_or(0);
+        let segment = open_segment(&dir, next_id, buffer_size);
+
+        let wal = Arc::new(Self {
+            dir,
+            buffer_size,
+            active: Mutex::new(segment),
+            sync_policy,
+            group: Mutex::new(GroupState { next_seq: 0, durable_seq: 0, syncing: false }),
+            group_cv: Condvar::new(),
+            periodic_stop: Arc::new(AtomicBool::new(false)),
+            periodic_thread: Mutex::new(None),
+        });
+
+        if let WalSyncPolicy::Periodic(interval) = sync_policy {
+            let weak: Weak<DiskWal> = Arc::downgrade(&wal);
+            let stop = wal.periodic_stop.clone();
+            // Sleep in short ticks rather than the full interval at once, so
+            // `Drop` (which sets `stop`) doesn't have to wait out a long
+            // period before this thread notices and exits.
+            let tick = interval.min(Duration::from_millis(50)).max(Duration::from_millis(1));
+            let handle = thread::spawn(move || {
+                let mut elapsed = Duration::ZERO;
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(tick);
+                    elapsed += tick;
+                    if elapsed < interval {
+                        continue;
+                    }
+                    elapsed = Duration::ZERO;
+                    match weak.upgrade() {
+                        Some(wal) => {
+                            let _ = wal.active.lock().unwrap().flush();
+                        }
+                        None => break,
+                    }
+                }
+            });
+            *wal.periodic_thread.lock().unwrap() = Some(handle);
+        }
+
+        Ok(wal)
+    }
+
+    /// Wait until a Group-commit write at `seq` is durable, becoming the
+    /// designated syncer if no other writer is currently fsyncing.
+    fn group_commit(&self, seq: u64) -> Result<(), TsinkError> {
+        let mut state = self.group.lock().unwrap();
+        loop {
+            if state.durable_seq >= seq {
+                return Ok(());
+            }
+            if state.syncing {
+                state = self.group_cv.wait(state).unwrap();
+                continue;
+            }
+
+            state.syncing = true;
+            let syncing_to = state.next_seq;
+            drop(state);
+
+            let result = self.active.lock().unwrap().flush();
+
+            let mut state_after = self.group.lock().unwrap();
+            state_after.syncing = false;
+            if let Err(err) = result {
+                self.group_cv.notify_all();
+                return Err(err);
+            }
+            state_after.durable_seq = state_after.durable_seq.max(syncing_to);
+            self.group_cv.notify_all();
+            state = state_after;
+        }
+    }
+}
+
+impl Drop for DiskWal {
+    fn drop(&mut self) {
+        self.periodic_stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.periodic_thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn open_segment(dir: &Path, id: u64, buffer_size: usize) -> Segment {
+    Segment {
+        id,
+        path: segment_path(dir, id),
+        buffer_size,
+        writer: None,
+    }
+}
+
+/// Serialize a single row as `[metric][labels][timestamp][value]`.
+fn encode_row(row: &Row, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(row.metric().len() as u32).to_le_bytes());
+    out.extend_from_slice(row.metric().as_bytes());
+
+    out.extend_from_slice(&(row.labels().len() as u16).to_le_bytes());
+    for label in row.labels() {
+        out.extend_from_slice(&(label.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(label.name.as_bytes());
+        out.extend_from_slice(&(label.value.len() as u16).to_le_bytes());
+        out.extend_from_slice(label.value.as_bytes());
+    }
+
+    let point = row.data_point();
+    out.extend_from_slice(&point.timestamp.to_le_bytes());
+    out.extend_from_slice(&point.value.to_bits().to_le_bytes());
+}
+
+/// Inverse of [`encode_row`]. Returns the row and the number of bytes read.
+fn decode_row(buf: &[u8]) -> Result<(Row, usize), TsinkError> {
+    let mut cursor = 0usize;
+    let read_u16 = |buf: &[u8], at: usize| -> Result<u16, TsinkError> {
+        let bytes: [u8; 2] = buf
+            .get(at..at + 2)
+            .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?
+            .try_into()
+            .unwrap();
+        Ok(u16::from_le_bytes(bytes))
+    };
+    let read_u32 = |buf: &[u8], at: usize| -> Result<u32, TsinkError> {
+        let bytes: [u8; 4] = buf
+            .get(at..at + 4)
+            .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?
+            .try_into()
+            .unwrap();
+        Ok(u32::from_le_bytes(bytes))
+    };
+
+    let metric_len = read_u32(buf, cursor)? as usize;
+    cursor += 4;
+    let metric = std::str::from_utf8(
+        buf.get(cursor..cursor + metric_len)
+            .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?,
+    )
+    .map_err(|_| TsinkError::Corrupted("invalid utf8 in wal record".into()))?
+    .to_string();
+    cursor += metric_len;
+
+    let label_count = read_u16(buf, cursor)? as usize;
+    cursor += 2;
+    let mut labels = Vec::with_capacity(label_count);
+    for _ in 0..label_count {
+        let name_len = read_u16(buf, cursor)? as usize;
+        cursor += 2;
+        let name = std::str::from_utf8(
+            buf.get(cursor..cursor + name_len)
+                .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?,
+        )
+        .map_err(|_| TsinkError::Corrupted("invalid utf8 in wal record".into()))?
+        .to_string();
+        cursor += name_len;
+
+        let value_len = read_u16(buf, cursor)? as usize;
+        cursor += 2;
+        let value = std::str::from_utf8(
+            buf.get(cursor..cursor + value_len)
+                .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?,
+        )
+        .map_err(|_| TsinkError::Corrupted("invalid utf8 in wal record".into()))?
+        .to_string();
+        cursor += value_len;
+
+        labels.push(Label::new(name, value));
+    }
+
+    let timestamp_bytes: [u8; 8] = buf
+        .get(cursor..cursor + 8)
+        .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?
+        .try_into()
+        .unwrap();
+    let timestamp = i64::from_le_bytes(timestamp_bytes);
+    cursor += 8;
+
+    let value_bytes: [u8; 8] = buf
+        .get(cursor..cursor + 8)
+        .ok_or_else(|| TsinkError::Corrupted("truncated wal record".into()))?
+        .try_into()
+        .unwrap();
+    let value = f64::from_bits(u64::from_le_bytes(value_bytes));
+    cursor += 8;
+
+    Ok((
+        Row::with_labels(metric, labels, DataPoint::new(timestamp, value)),
+        cursor,
+    ))
+}
+
+impl Wal for DiskWal {
+    fn append_rows(&self, rows: &[Row]) -> Result<(), TsinkError> {
+        let seq = {
+            let mut segment = self.active.lock().unwrap();
+            for row in rows {
+                let mut encoded = Vec::new();
+                encode_row(row, &mut encoded);
+                write_record(segment.writer()?, &encoded)?;
+            }
+            let mut state = self.group.lock().unwrap();
+            state.next_seq += 1;
+            state.next_seq
+        };
+
+        match self.sync_policy {
+            WalSyncPolicy::Manual | WalSyncPolicy::Periodic(_) => {}
+            WalSyncPolicy::Always => self.active.lock().unwrap().flush()?,
+            WalSyncPolicy::Group => self.group_commit(seq)?,
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), TsinkError> {
+        self.active.lock().unwrap().flush()
+    }
+
+    fn punctuate(&self) -> Result<(), TsinkError> {
+        let mut segment = self.active.lock().unwrap();
+        segment.flush()?;
+        *segment = open_segment(&self.dir, segment.id + 1, self.buffer_size);
+        Ok(())
+    }
+
+    fn remove_oldest(&self) -> Result<(), TsinkError> {
+        let segment = self.active.lock().unwrap();
+        if let Some((_, path)) = segment_files(&self.dir)?
+            .into_iter()
+            .find(|(id, _)| *id != segment.id)
+        {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn refresh(&self) -> Result<(), TsinkError> {
+        let mut segment = self.active.lock().unwrap();
+        let stale_id = segment.id;
+        // The new segment is lazily created, so it has no file yet; a fully
+        // refreshed WAL directory looks empty until the next append.
+        *segment = open_segment(&self.dir, stale_id + 1, self.buffer_size);
+        for (id, path) in segment_files(&self.dir)? {
+            if id <= stale_id {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads back the rows appended to a WAL directory, oldest segment first.
+pub struct WalReader {
+    dir: PathBuf,
+}
+
+impl WalReader {
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, TsinkError> {
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Read every recoverable row across all segments, in append order.
+    ///
+    /// Recovery stops at the first sign of a torn write — a frame whose
+    /// length runs past EOF, a checksum mismatch, or a fragment chain left
+    /// incomplete by a crash mid-append — and returns everything recovered
+    /// up to that point rather than failing the whole read.
+    pub fn read_all(&self) -> Result<Vec<Row>, TsinkError> {
+        let mut rows = Vec::new();
+        // Chunks of a record currently being reassembled from First/Middle
+        // fragments, carried across frames (and, in principle, segments).
+        let mut pending: Option<Vec<u8>> = None;
+
+        'segments: for (_, path) in segment_files(&self.dir)? {
+            let mut file = File::open(&path)?;
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+
+            let mut cursor = 0usize;
+            loop {
+                let frame = match read_frame(&data, cursor) {
+                    Ok(Some((frame, next))) => {
+                        cursor = next;
+                        frame
+                    }
+                    Ok(None) => break,
+                    Err(_) => break 'segments,
+                };
+
+                match frame.fragment_type {
+                    FragmentType::Full => {
+                        if pending.is_some() {
+                            // A new record started without a preceding Last;
+                            // the in-progress chain can never complete.
+                            break 'segments;
+                        }
+                        let (row, _) = decode_row(&frame.payload)?;
+                        rows.push(row);
+                    }
+                    FragmentType::First => {
+                        if pending.is_some() {
+                            break 'segments;
+                        }
+                        pending = Some(frame.payload);
+                    }
+                    FragmentType::Middle => match &mut pending {
+                        Some(buf) => buf.extend_from_slice(&frame.payload),
+                        None => break 'segments,
+                    },
+                    FragmentType::Last => match pending.take() {
+                        Some(mut buf) => {
+                            buf.extend_from_slice(&frame.payload);
+                            let (row, _) = decode_row(&buf)?;
+                            rows.push(row);
+                        }
+                        None => break 'segments,
+                    },
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+}
@@ -327,3 +327,188 @@ fn test_select_across_multiple_partitions_persistent() {
     );
     assert!(points.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
 }
+
+#[test]
+fn test_select_cursor_merges_partitions_lazily() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = StorageBuilder::new()
+        .with_timestamp_precision(TimestampPrecision::Seconds)
+        .with_partition_duration(Duration::from_secs(2))
+        .with_data_path(temp_dir.path())
+        .build()
+        .unwrap();
+
+    // Force a rotation so the cursor has to merge across a disk partition
+    // and the in-memory head.
+    storage
+        .insert_rows(&[Row::new("cursor_metric", DataPoint::new(1, 1.0))])
+        .unwrap();
+    storage
+        .insert_rows(&[Row::new("cursor_metric", DataPoint::new(5, 2.0))])
+        .unwrap();
+    storage
+        .insert_rows(&[Row::new("cursor_metric", DataPoint::new(3, 3.0))])
+        .unwrap();
+
+    let mut cursor = storage.select_cursor("cursor_metric", &[], 0, 20).unwrap();
+    let timestamps: Vec<i64> = std::iter::from_fn(|| cursor.next())
+        .map(|point| point.expect("cursor should not error").timestamp)
+        .collect();
+
+    assert_eq!(timestamps, vec![1, 3, 5]);
+
+    // select() is defined in terms of select_cursor() and must agree.
+    let collected = storage.select("cursor_metric", &[], 0, 20).unwrap();
+    assert_eq!(
+        collected.iter().map(|p| p.timestamp).collect::<Vec<_>>(),
+        timestamps
+    );
+}
+
+#[test]
+fn test_select_spans_multiple_on_disk_blocks() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = StorageBuilder::new()
+        .with_timestamp_precision(TimestampPrecision::Seconds)
+        .with_partition_duration(Duration::from_secs(10_000))
+        .with_data_path(temp_dir.path())
+        .build()
+        .unwrap();
+
+    // More points than fit in a single on-disk block, so the lazily-decoded
+    // disk cursor must walk several blocks to reproduce the full series.
+    let rows: Vec<Row> = (0..500)
+        .map(|i| Row::new("block_spanning", DataPoint::new(i, i as f64)))
+        .collect();
+    storage.insert_rows(&rows).unwrap();
+    storage.close().unwrap();
+
+    let storage = StorageBuilder::new()
+        .with_timestamp_precision(TimestampPrecision::Seconds)
+        .with_partition_duration(Duration::from_secs(10_000))
+        .with_data_path(temp_dir.path())
+        .with_wal_enabled(false)
+        .build()
+        .unwrap();
+
+    let points = storage.select("block_spanning", &[], 0, 499).unwrap();
+    assert_eq!(points.len(), 500);
+    assert!(points.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+    for (i, point) in points.iter().enumerate() {
+        assert_eq!(point.timestamp, i as i64);
+        assert_eq!(point.value, i as f64);
+    }
+
+    // A sub-range that only overlaps the tail blocks should skip the rest
+    // without decoding them, but still return exactly the matching points.
+    let tail = storage.select("block_spanning", &[], 450, 499).unwrap();
+    assert_eq!(tail.len(), 50);
+    assert_eq!(tail[0].timestamp, 450);
+    assert_eq!(tail[49].timestamp, 499);
+}
+
+#[test]
+fn test_concurrent_inserts_never_double_flush_a_rotation() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(
+        StorageBuilder::new()
+            .with_timestamp_precision(TimestampPrecision::Seconds)
+            .with_partition_duration(Duration::from_millis(1))
+            .with_data_path(temp_dir.path())
+            .with_wal_enabled(false)
+            .build()
+            .unwrap(),
+    );
+
+    const WRITER_THREADS: i64 = 8;
+    const POINTS_PER_THREAD: i64 = 250;
+
+    let handles: Vec<_> = (0..WRITER_THREADS)
+        .map(|thread_index| {
+            let storage = storage.clone();
+            thread::spawn(move || {
+                for i in 0..POINTS_PER_THREAD {
+                    let timestamp = thread_index * POINTS_PER_THREAD + i;
+                    storage
+                        .insert_rows(&[Row::new("race_metric", DataPoint::new(timestamp, timestamp as f64))])
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    storage.close().unwrap();
+
+    let points = storage
+        .select("race_metric", &[], 0, WRITER_THREADS * POINTS_PER_THREAD)
+        .unwrap();
+    assert_eq!(
+        points.len(),
+        (WRITER_THREADS * POINTS_PER_THREAD) as usize,
+        "a racing rotation must not flush the same points into more than one partition"
+    );
+}
+
+#[test]
+fn test_concurrent_reads_never_regress_during_rotation() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(
+        StorageBuilder::new()
+            .with_timestamp_precision(TimestampPrecision::Seconds)
+            .with_partition_duration(Duration::from_millis(1))
+            .with_data_path(temp_dir.path())
+            .with_wal_enabled(false)
+            .build()
+            .unwrap(),
+    );
+
+    const TOTAL_POINTS: i64 = 2000;
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+    let writer = {
+        let storage = storage.clone();
+        thread::spawn(move || {
+            for timestamp in 0..TOTAL_POINTS {
+                storage
+                    .insert_rows(&[Row::new("regress_metric", DataPoint::new(timestamp, timestamp as f64))])
+                    .unwrap();
+            }
+        })
+    };
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let storage = storage.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut max_seen = 0usize;
+                while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                    let points = storage
+                        .select("regress_metric", &[], 0, TOTAL_POINTS)
+                        .expect("select should not error during a concurrent rotation");
+                    assert!(
+                        points.len() >= max_seen,
+                        "select() returned {} points after previously observing {} — \
+                         a concurrent rotation made already-visible points disappear",
+                        points.len(),
+                        max_seen
+                    );
+                    max_seen = points.len();
+                }
+            })
+        })
+        .collect();
+
+    writer.join().unwrap();
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    storage.close().unwrap();
+    let points = storage.select("regress_metric", &[], 0, TOTAL_POINTS).unwrap();
+    assert_eq!(points.len(), TOTAL_POINTS as usize);
+}